@@ -0,0 +1,138 @@
+// Copyright 2021 Rafael Mardojai CM
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub mod card;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use image::DynamicImage;
+use isahc::ReadResponseExt;
+use scraper::{Html, Selector};
+use url::Url;
+
+pub use card::{Card, CardError, CardSize, Social};
+
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub url: String,
+    pub title: Option<String>,
+    // Meta-tag values keyed by property/name. A key maps to *all* of its values
+    // in document order, because tags like `og:image` legitimately repeat for
+    // multi-image galleries and collapsing them would lose the gallery.
+    pub metadata: HashMap<String, Vec<String>>,
+    pub images: Vec<Image>,
+    pub ld_json: Vec<String>,
+}
+
+impl Data {
+    pub fn from_html(url: &str, body: &str) -> Data {
+        //! Scrape a fetched document into the metadata the cards consume: the
+        //! `<title>`, every OpenGraph/Twitter `<meta>` pair, every `<img>` source
+        //! that is an absolute URL (relative sources are skipped) and every
+        //! `application/ld+json` block.
+
+        let document = Html::parse_document(body);
+
+        let title = Selector::parse("title").ok()
+            .and_then(|selector| document.select(&selector).next())
+            .map(|element| element.text().collect::<String>().trim().to_string())
+            .filter(|text| !text.is_empty());
+
+        let mut metadata = HashMap::new();
+        if let Ok(selector) = Selector::parse("meta") {
+            for element in document.select(&selector) {
+                let key = element.value().attr("property")
+                    .or_else(|| element.value().attr("name"));
+                if let (Some(key), Some(content)) = (key, element.value().attr("content")) {
+                    metadata.entry(key.to_string()).or_default().push(content.to_string());
+                }
+            }
+        }
+
+        let mut images = Vec::new();
+        if let Ok(selector) = Selector::parse("img") {
+            for element in document.select(&selector) {
+                if let Some(src) = element.value().attr("src") {
+                    if Url::parse(src).is_ok() {
+                        images.push(Image::new(src.to_string()));
+                    }
+                }
+            }
+        }
+
+        let ld_json = Data::scrape_ld_json(&document);
+
+        Data {url: url.to_string(), title, metadata, images, ld_json}
+    }
+
+    fn scrape_ld_json(document: &Html) -> Vec<String> {
+        //! Collect the raw body of every `<script type="application/ld+json">`
+        //! block; parsing and flattening happen later in `Card::flatten_jsonld`.
+
+        let mut scripts = Vec::new();
+        if let Ok(selector) = Selector::parse(r#"script[type="application/ld+json"]"#) {
+            for element in document.select(&selector) {
+                let body = element.text().collect::<String>();
+                if !body.trim().is_empty() {
+                    scripts.push(body);
+                }
+            }
+        }
+        scripts
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Image {
+    pub url: String,
+    // Downloaded bytes and decoded dimensions, filled on first access and reused
+    // by validation, gallery collection and rendering so the image is fetched
+    // at most once and its dimensions decoded at most once; `decode` itself is
+    // not cached, so rendering decodes the bytes again. Cloning an `Image`
+    // carries the cache with it.
+    cache: RefCell<Option<Vec<u8>>>,
+    dimensions: RefCell<Option<(u32, u32)>>,
+}
+
+impl Image {
+    pub fn new(url: String) -> Image {
+        //! Reference a remote image by URL; bytes are fetched lazily on demand.
+
+        Image {url, cache: RefCell::new(None), dimensions: RefCell::new(None)}
+    }
+
+    fn ensure_bytes(&self) -> Option<Vec<u8>> {
+        //! Download the image once, caching the bytes for later reuse.
+
+        if self.cache.borrow().is_none() {
+            let bytes = isahc::get(&self.url).ok()
+                .and_then(|mut response| response.bytes().ok())?;
+            *self.cache.borrow_mut() = Some(bytes);
+        }
+        self.cache.borrow().clone()
+    }
+
+    pub fn size_bytes(&self) -> Option<u64> {
+        //! The downloaded byte size of the image, fetching it if needed.
+
+        self.ensure_bytes().map(|bytes| bytes.len() as u64)
+    }
+
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        //! The decoded pixel dimensions of the image, decoded once and cached.
+
+        if self.dimensions.borrow().is_none() {
+            let decoded = self.decode()?;
+            *self.dimensions.borrow_mut() = Some((decoded.width(), decoded.height()));
+        }
+        *self.dimensions.borrow()
+    }
+
+    pub fn decode(&self) -> Option<DynamicImage> {
+        //! Decode the cached bytes into a `DynamicImage`, fetching them if needed.
+
+        let bytes = self.ensure_bytes()?;
+        image::load_from_memory(&bytes).ok()
+    }
+}